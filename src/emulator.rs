@@ -1,14 +1,65 @@
-use std::time::Instant;
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 
 use opcode::Opcode;
 
+mod error;
+pub use error::{Error, ErrorKind};
+
+const STACK_CAPACITY: usize = 24;
+
+/// Bumped whenever the save-state layout changes, so `load_state` rejects data
+/// written with any other version instead of silently misreading it.
+const SAVE_STATE_VERSION: u8 = 1;
+
+const AUDIO_TONE_HZ: f32 = 440.0;
+const AUDIO_SAMPLE_RATE: u32 = 44_100;
+const AUDIO_LOWPASS_ALPHA: f32 = 0.15;
+const AUDIO_RAMP_SAMPLES: f32 = 64.0;
+/// Caps buffered-but-undrained audio so a front-end that never calls `drain_audio`
+/// can't leak memory for the life of the process.
+const AUDIO_BUFFER_CAPACITY: usize = AUDIO_SAMPLE_RATE as usize / 4;
+
+const DEFAULT_CLOCK_HZ: u32 = 700;
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+/// The delay/sound timers always count down at 60 Hz, independent of `clock_hz`.
+const TIMER_PERIOD_NS: u64 = NANOS_PER_SEC / 60;
+
 pub type Address = u16;
 pub type Constant = u8;
 pub type Register = u8;
 
 const WORD_SIZE: u16 = 2;
 
+const FONT_SET: [u8; 16 * 5] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+const FONT_SPRITE_BYTES: u16 = 5;
+
+pub const DISPLAY_WIDTH: usize = 64;
+pub const DISPLAY_HEIGHT: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DrawMode {
+    Wrap,
+    Clip,
+}
+
 pub struct System {
     memory: [Constant; 4096],
     registers: [Constant; 16],
@@ -18,13 +69,81 @@ pub struct System {
     sound_timer: Constant,
     program_counter: Address,
 
-    last_tick: Instant,
+    display: [bool; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+    draw_mode: DrawMode,
+
+    keys: [bool; 16],
+    rng_state: u32,
+
+    audio_buffer: VecDeque<f32>,
+    audio_phase: f32,
+    audio_envelope: f32,
+    audio_filter_state: f32,
+    audio_sample_debt: f32,
+
+    clock_hz: u32,
+    timer_debt_ns: u64,
+
+    breakpoints: HashSet<Address>,
+    suppressed_breakpoint: Option<Address>,
+}
+
+fn xorshift32(state: &mut u32) -> u32 {
+    let mut x = *state;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    *state = x;
+    x
+}
+
+struct StateCursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateCursor<'a> {
+    fn new(data: &'a [u8]) -> StateCursor<'a> {
+        StateCursor { data, position: 0 }
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let byte = *self.data.get(self.position).ok_or_else(StateCursor::truncated)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        let low = self.read_u8()?;
+        let high = self.read_u8()?;
+        Ok(u16::from_le_bytes([low, high]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.read_array::<4>()?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let slice = self.data.get(self.position..self.position + N).ok_or_else(StateCursor::truncated)?;
+        self.position += N;
+
+        let mut array = [0; N];
+        array.copy_from_slice(slice);
+        Ok(array)
+    }
+
+    fn truncated() -> Error {
+        Error::invalid_save_state("save state data ended unexpectedly".to_string())
+    }
 }
 
 impl System {
     pub fn new(program: &[u8]) -> System {
         let mut memory = [0; 4096];
 
+        memory[0..FONT_SET.len()].copy_from_slice(&FONT_SET);
+
         let mut current_address = 0x200;
         for byte in program {
             if current_address == 0xEA0 {
@@ -39,61 +158,295 @@ impl System {
             memory,
             registers: [0; 16],
             address_register: 0x0,
-            stack: VecDeque::with_capacity(24),
+            stack: VecDeque::with_capacity(STACK_CAPACITY),
             delay_timer: 0,
             sound_timer: 0,
             program_counter: 0x200,
 
-            last_tick: Instant::now()
+            display: [false; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            draw_mode: DrawMode::Wrap,
+
+            keys: [false; 16],
+            rng_state: 0xDEAD_BEEF,
+
+            audio_buffer: VecDeque::new(),
+            audio_phase: 0.0,
+            audio_envelope: 0.0,
+            audio_filter_state: 0.0,
+            audio_sample_debt: 0.0,
+
+            clock_hz: DEFAULT_CLOCK_HZ,
+            timer_debt_ns: 0,
+
+            breakpoints: HashSet::new(),
+            suppressed_breakpoint: None,
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, addr: Address) {
+        self.breakpoints.insert(addr);
+        if self.suppressed_breakpoint == Some(addr) {
+            self.suppressed_breakpoint = None;
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: Address) {
+        self.breakpoints.remove(&addr);
+        if self.suppressed_breakpoint == Some(addr) {
+            self.suppressed_breakpoint = None;
+        }
+    }
+
+    pub fn dump_state(&self) {
+        println!("PC: {:#06x}    I: {:#06x}    DELAY: {:3}    SOUND: {:3}",
+            self.program_counter, self.address_register, self.delay_timer, self.sound_timer);
+
+        for row in 0..4 {
+            for col in 0..4 {
+                let register = row * 4 + col;
+                print!("V{:X}: {:#04x}  ", register, self.registers[register as usize]);
+            }
+            println!();
+        }
+
+        print!("STACK:");
+        for address in self.stack.iter() {
+            print!(" {:#06x}", address);
         }
+        println!();
     }
 
-    pub fn run(&mut self) {
-        println!("PC\tDELAY\tSOUND\tOP\tARG1\tARG2\tARG3");
-        println!("--\t-----\t-----\t--\t----\t----\t----");
+    pub fn disassemble(&self, addr: Address) -> String {
+        let first_byte = match self.read_memory(addr) {
+            Ok(byte) => byte,
+            Err(_) => return format!("{:#06x}: <out of bounds>", addr),
+        };
+        let second_byte = match self.read_memory(addr + 1) {
+            Ok(byte) => byte,
+            Err(_) => return format!("{:#06x}: <out of bounds>", addr),
+        };
+
+        match Opcode::from(first_byte, second_byte) {
+            Ok(opcode) => format!("{:#06x}: {:?}", addr, opcode),
+            Err((first, second)) => format!("{:#06x}: DATA {:02x}{:02x}", addr, first.0, second.0),
+        }
+    }
+
+    /// Clamped to at least 1, since 0 would make `step` divide by zero.
+    pub fn set_clock_hz(&mut self, hz: u32) {
+        self.clock_hz = hz.max(1);
+    }
+
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.keys[(key & 0xF) as usize] = pressed;
+    }
+
+    pub fn seed_rng(&mut self, seed: u32) {
+        self.rng_state = if seed == 0 { 0xDEAD_BEEF } else { seed };
+    }
+
+    pub fn framebuffer(&self) -> &[bool] {
+        &self.display
+    }
+
+    pub fn set_draw_mode(&mut self, mode: DrawMode) {
+        self.draw_mode = mode;
+    }
+
+    pub fn drain_audio(&mut self, out: &mut [f32]) {
+        for slot in out.iter_mut() {
+            *slot = self.audio_buffer.pop_front().unwrap_or(0.0);
+        }
+    }
+
+    /// Consumes the same `instruction_ns` passed to `service_timers`, so the waveform
+    /// stays in sync with `sound_timer` regardless of how fast `step` is actually
+    /// being driven in real time.
+    fn service_audio(&mut self, elapsed_ns: u64) {
+        self.audio_sample_debt += (elapsed_ns as f64 / NANOS_PER_SEC as f64) as f32 * AUDIO_SAMPLE_RATE as f32;
+        let samples_due = self.audio_sample_debt as usize;
+        self.audio_sample_debt -= samples_due as f32;
+
+        self.generate_audio_samples(samples_due);
+    }
+
+    fn generate_audio_samples(&mut self, count: usize) {
+        let target_envelope = if self.sound_timer > 0 { 1.0 } else { 0.0 };
+        let ramp_step = 1.0 / AUDIO_RAMP_SAMPLES;
+
+        for _ in 0..count {
+            if self.audio_envelope < target_envelope {
+                self.audio_envelope = (self.audio_envelope + ramp_step).min(target_envelope);
+            } else if self.audio_envelope > target_envelope {
+                self.audio_envelope = (self.audio_envelope - ramp_step).max(target_envelope);
+            }
+
+            let square = if self.audio_phase < 0.5 { 1.0 } else { -1.0 };
+            let raw_sample = square * self.audio_envelope;
+
+            self.audio_filter_state += AUDIO_LOWPASS_ALPHA * (raw_sample - self.audio_filter_state);
+
+            if self.audio_buffer.len() >= AUDIO_BUFFER_CAPACITY {
+                self.audio_buffer.pop_front();
+            }
+            self.audio_buffer.push_back(self.audio_filter_state);
+
+            self.audio_phase = (self.audio_phase + AUDIO_TONE_HZ / AUDIO_SAMPLE_RATE as f32).fract();
+        }
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(4096 + 16 + 2 + 2 * STACK_CAPACITY + 1 + 1 + 2 + DISPLAY_WIDTH * DISPLAY_HEIGHT + 16 + 4);
+
+        data.push(SAVE_STATE_VERSION);
+
+        data.extend_from_slice(&self.memory);
+        data.extend_from_slice(&self.registers);
+        data.extend_from_slice(&self.address_register.to_le_bytes());
+
+        data.push(self.stack.len() as u8);
+        for address in self.stack.iter() {
+            data.extend_from_slice(&address.to_le_bytes());
+        }
+
+        data.push(self.delay_timer);
+        data.push(self.sound_timer);
+        data.extend_from_slice(&self.program_counter.to_le_bytes());
+
+        data.extend(self.display.iter().map(|&pixel| pixel as u8));
+        data.extend(self.keys.iter().map(|&pressed| pressed as u8));
+
+        data.extend_from_slice(&self.rng_state.to_le_bytes());
+
+        data
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Error> {
+        let mut cursor = StateCursor::new(data);
+
+        let version = cursor.read_u8()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(Error::invalid_save_state(format!(
+                "unsupported save state version {} (expected {})",
+                version, SAVE_STATE_VERSION
+            )));
+        }
+
+        let memory = cursor.read_array::<4096>()?;
+        let registers = cursor.read_array::<16>()?;
+        let address_register = cursor.read_u16()?;
+
+        let stack_len = cursor.read_u8()? as usize;
+        if stack_len > STACK_CAPACITY {
+            return Err(Error::invalid_save_state(format!(
+                "save state stack length {} exceeds capacity of {}",
+                stack_len, STACK_CAPACITY
+            )));
+        }
+        let mut stack = VecDeque::with_capacity(STACK_CAPACITY);
+        for _ in 0..stack_len {
+            stack.push_back(cursor.read_u16()?);
+        }
+
+        let delay_timer = cursor.read_u8()?;
+        let sound_timer = cursor.read_u8()?;
+        let program_counter = cursor.read_u16()?;
+
+        let mut display = [false; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        for pixel in display.iter_mut() {
+            *pixel = cursor.read_u8()? != 0;
+        }
+
+        let mut keys = [false; 16];
+        for key in keys.iter_mut() {
+            *key = cursor.read_u8()? != 0;
+        }
+
+        let rng_state = cursor.read_u32()?;
+
+        self.memory = memory;
+        self.registers = registers;
+        self.address_register = address_register;
+        self.stack = stack;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.program_counter = program_counter;
+        self.display = display;
+        self.keys = keys;
+        self.rng_state = rng_state;
+
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> Error {
         loop {
-            let first_address = self.program_counter as usize;
-            let second_address = (self.program_counter + 1) as usize;
-
-            let first_byte = self.memory[first_address];
-            let second_byte = self.memory[second_address];
-
-            print!("{:#04x}\t{}\t{}\t", self.program_counter, self.delay_timer, self.sound_timer);
-            match Opcode::from(first_byte, second_byte) {
-                Ok(opcode) => {
-                    println!("{:?}", opcode);
-                    self.process_opcode(opcode);
-                },
-                Err((first, second)) => {
-                    println!("DATA\t{:x}{:x}", first.0, second.0);
-                    self.program_counter += WORD_SIZE;
-                }
+            if let Err(error) = self.step() {
+                return error;
             }
+        }
+    }
+
+    /// A breakpoint traps once; calling `step` again at the same address executes the
+    /// instruction there and re-arms it, so the next step that starts at that address
+    /// (even one that never left it, such as a jump to itself) traps again.
+    pub fn step(&mut self) -> Result<(), Error> {
+        if self.breakpoints.contains(&self.program_counter) {
+            if self.suppressed_breakpoint == Some(self.program_counter) {
+                self.suppressed_breakpoint = None;
+            } else {
+                self.suppressed_breakpoint = Some(self.program_counter);
+                return Err(Error::breakpoint(self.program_counter));
+            }
+        }
+
+        let first_byte = self.read_memory(self.program_counter)?;
+        let second_byte = self.read_memory(self.program_counter + 1)?;
+
+        match Opcode::from(first_byte, second_byte) {
+            Ok(opcode) => self.process_opcode(opcode)?,
+            Err((first, second)) => return Err(Error::invalid_opcode(first.0, second.0)),
+        }
+
+        let instruction_ns = NANOS_PER_SEC / self.clock_hz as u64;
+        self.service_timers(instruction_ns);
+        self.service_audio(instruction_ns);
+
+        Ok(())
+    }
+
+    fn read_memory(&self, address: Address) -> Result<Constant, Error> {
+        self.memory.get(address as usize).copied().ok_or_else(|| Error::memory_out_of_bounds(address))
+    }
 
-            self.tick(60);
+    fn write_memory(&mut self, address: Address, value: Constant) -> Result<(), Error> {
+        match self.memory.get_mut(address as usize) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err(Error::memory_out_of_bounds(address)),
         }
     }
 
-    fn process_opcode(&mut self, opcode: Opcode) {
+    fn process_opcode(&mut self, opcode: Opcode) -> Result<(), Error> {
         match opcode {
             Opcode::Call(address) => {
                 self.program_counter += WORD_SIZE;
             }
             Opcode::Clear => {
+                self.display = [false; DISPLAY_WIDTH * DISPLAY_HEIGHT];
                 self.program_counter += WORD_SIZE;
             }
             Opcode::Return => {
-                if let Some(address) = self.stack.pop_front() {
-                    self.program_counter = address;
-                } else {
-                    println!("NOWHERE TO RETURN");
-                    self.program_counter += WORD_SIZE;
-                }
+                self.program_counter = self.stack.pop_front().ok_or_else(Error::stack_underflow)?;
             }
             Opcode::Goto(address) => {
                 self.program_counter = address;
             }
             Opcode::CallFunction(address) => {
+                if self.stack.len() >= STACK_CAPACITY {
+                    return Err(Error::stack_overflow(STACK_CAPACITY));
+                }
                 self.stack.push_front(self.program_counter);
                 self.program_counter = address;
             }
@@ -219,20 +572,66 @@ impl System {
                 self.program_counter = self.get_register(0x0) as u16 + self.address_register;
             }
             Opcode::SetRand(register, constant) => {
+                let byte = xorshift32(&mut self.rng_state) as u8;
+                self.set_register(register, byte & constant);
                 self.program_counter += WORD_SIZE;
             }
             Opcode::Draw(first, second, constant) => {
+                let origin_x = self.get_register(first) as usize % DISPLAY_WIDTH;
+                let origin_y = self.get_register(second) as usize % DISPLAY_HEIGHT;
+
+                let mut flipped = false;
+                for row in 0..(constant as usize) {
+                    let sprite_byte = self.read_memory(self.address_register + row as u16)?;
+
+                    for col in 0..8 {
+                        if sprite_byte & (0x80 >> col) == 0 {
+                            continue;
+                        }
+
+                        let (x, y) = match self.pixel_coords(origin_x + col, origin_y + row) {
+                            Some(coords) => coords,
+                            None => continue,
+                        };
+
+                        let index = y * DISPLAY_WIDTH + x;
+                        if self.display[index] {
+                            flipped = true;
+                        }
+                        self.display[index] ^= true;
+                    }
+                }
+
+                self.set_flag_register(if flipped { 1 } else { 0 });
                 self.program_counter += WORD_SIZE;
             }
-            Opcode::SkipKeyPress(register) => {}
-            Opcode::SkipNoKeyPress(register) => {}
+            Opcode::SkipKeyPress(register) => {
+                let key = self.get_register(register) & 0xF;
+                if self.keys[key as usize] {
+                    self.program_counter += 2 * WORD_SIZE;
+                } else {
+                    self.program_counter += WORD_SIZE;
+                }
+            }
+            Opcode::SkipNoKeyPress(register) => {
+                let key = self.get_register(register) & 0xF;
+                if !self.keys[key as usize] {
+                    self.program_counter += 2 * WORD_SIZE;
+                } else {
+                    self.program_counter += WORD_SIZE;
+                }
+            }
             Opcode::StoreDelayTimer(register) => {
                 let delay = self.delay_timer;
                 self.set_register(register, delay);
                 self.program_counter += WORD_SIZE;
             }
             Opcode::StoreKeypress(register) => {
-                self.program_counter += WORD_SIZE;
+                // Blocking: leave the program counter untouched until a key goes down.
+                if let Some(key) = (0u8..16).find(|&k| self.keys[k as usize]) {
+                    self.set_register(register, key);
+                    self.program_counter += WORD_SIZE;
+                }
             }
             Opcode::SetDelayTimer(register) => {
                 self.delay_timer = self.get_register(register);
@@ -243,50 +642,75 @@ impl System {
                 self.program_counter += WORD_SIZE;
             }
             Opcode::IncrementAddressReg(register) => {
-                self.address_register += self.get_register(register) as u16;
+                let increment = self.get_register(register) as u16;
+                self.address_register = self.address_register
+                    .checked_add(increment)
+                    .ok_or_else(|| Error::memory_out_of_bounds(self.address_register))?;
                 self.program_counter += WORD_SIZE;
             }
             Opcode::StoreSpriteAddress(register) => {
+                let digit = self.get_register(register) & 0xF;
+                self.address_register = FONT_SPRITE_BYTES * digit as u16;
                 self.program_counter += WORD_SIZE;
             }
             Opcode::BinaryCodedDecimal(register) => {
+                let value = self.get_register(register);
+                let address = self.address_register;
+
+                self.write_memory(address, value / 100)?;
+                self.write_memory(address + 1, (value / 10) % 10)?;
+                self.write_memory(address + 2, value % 10)?;
+
                 self.program_counter += WORD_SIZE;
             }
             Opcode::Dump(register) => {
                 for i in 0..(register + 1) {
-                    let to_register = self.address_register as u8;
                     let value = self.get_register(i);
-
-                    self.set_register(to_register, value);
-                    self.address_register += 1;
+                    self.write_memory(self.address_register + i as u16, value)?;
                 }
+                self.address_register += register as u16 + 1;
                 self.program_counter += WORD_SIZE;
             }
             Opcode::Load(register) => {
                 for i in 0..(register + 1) {
-                    let from_register = self.address_register as u8;
-                    let value = self.get_register(from_register);
-
+                    let value = self.read_memory(self.address_register + i as u16)?;
                     self.set_register(i, value);
-                    self.address_register += 1;
                 }
+                self.address_register += register as u16 + 1;
                 self.program_counter += WORD_SIZE;
             }
         }
+
+        Ok(())
     }
 
-    fn tick(&mut self, rate: u8) {
-        let elapsed = (self.last_tick.elapsed().as_secs() % (rate as u64)) as u8;
+    fn service_timers(&mut self, elapsed_ns: u64) {
+        self.timer_debt_ns += elapsed_ns;
 
-        if self.delay_timer > 0 {
-            self.delay_timer -= elapsed;
-        }
+        while self.timer_debt_ns >= TIMER_PERIOD_NS {
+            self.timer_debt_ns -= TIMER_PERIOD_NS;
+
+            if self.delay_timer > 0 {
+                self.delay_timer -= 1;
+            }
 
-        if self.sound_timer > 0 {
-            self.sound_timer -= elapsed;
+            if self.sound_timer > 0 {
+                self.sound_timer -= 1;
+            }
         }
+    }
 
-        self.last_tick = Instant::now();
+    fn pixel_coords(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        match self.draw_mode {
+            DrawMode::Wrap => Some((x % DISPLAY_WIDTH, y % DISPLAY_HEIGHT)),
+            DrawMode::Clip => {
+                if x < DISPLAY_WIDTH && y < DISPLAY_HEIGHT {
+                    Some((x, y))
+                } else {
+                    None
+                }
+            }
+        }
     }
 
     fn get_register(&self, register: Register) -> Constant {
@@ -300,4 +724,113 @@ impl System {
     fn set_flag_register(&mut self, value: Constant) {
         self.registers[15] = value;
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_rand_program(register: u8, mask: u8) -> [u8; 2] {
+        [0xC0 | register, mask]
+    }
+
+    #[test]
+    fn set_rand_is_deterministic_given_the_same_seed() {
+        let mut a = System::new(&set_rand_program(0, 0xFF));
+        a.seed_rng(1234);
+        a.step().unwrap();
+
+        let mut b = System::new(&set_rand_program(0, 0xFF));
+        b.seed_rng(1234);
+        b.step().unwrap();
+
+        assert_eq!(a.get_register(0), b.get_register(0));
+    }
+
+    #[test]
+    fn save_state_round_trips_state_needed_for_deterministic_replay() {
+        let program = [0xC0, 0xFF, 0xC0, 0xFF];
+        let mut original = System::new(&program);
+        original.seed_rng(42);
+        original.step().unwrap();
+
+        let snapshot = original.save_state();
+
+        let mut restored = System::new(&[]);
+        restored.load_state(&snapshot).unwrap();
+
+        assert_eq!(restored.program_counter, original.program_counter);
+        assert_eq!(restored.get_register(0), original.get_register(0));
+
+        // If `rng_state` hadn't round-tripped, this next draw would diverge from
+        // continuing to step the original.
+        original.step().unwrap();
+        restored.step().unwrap();
+        assert_eq!(restored.get_register(0), original.get_register(0));
+    }
+
+    fn draw_one_row_program() -> [u8; 2] {
+        // D01: draw a 1-row sprite at (V0, V1).
+        [0xD0, 0x11]
+    }
+
+    fn system_with_sprite(sprite_byte: u8) -> System {
+        let mut sys = System::new(&draw_one_row_program());
+        sys.address_register = 0x300;
+        sys.write_memory(0x300, sprite_byte).unwrap();
+        sys
+    }
+
+    #[test]
+    fn draw_xors_a_sprite_into_the_framebuffer_and_flags_collisions() {
+        let mut sys = system_with_sprite(0xFF);
+        sys.set_register(0, 0);
+        sys.set_register(1, 0);
+
+        sys.step().unwrap();
+        assert!(sys.display[0..8].iter().all(|&pixel| pixel));
+        assert_eq!(sys.get_register(0xF), 0);
+
+        sys.program_counter = 0x200;
+        sys.step().unwrap();
+        assert!(sys.display[0..8].iter().all(|&pixel| !pixel));
+        assert_eq!(sys.get_register(0xF), 1);
+    }
+
+    #[test]
+    fn draw_wraps_the_origin_around_the_screen_edges() {
+        let mut sys = system_with_sprite(0x80);
+        sys.set_register(0, DISPLAY_WIDTH as u8 + 4);
+        sys.set_register(1, 0);
+
+        sys.step().unwrap();
+        assert!(sys.display[4]);
+    }
+
+    #[test]
+    fn draw_clips_pixels_that_fall_off_the_wrapped_origin() {
+        let mut sys = system_with_sprite(0xFF);
+        sys.set_draw_mode(DrawMode::Clip);
+        sys.set_register(0, DISPLAY_WIDTH as u8 - 4);
+        sys.set_register(1, 0);
+
+        sys.step().unwrap();
+        assert!(sys.display[DISPLAY_WIDTH - 4..DISPLAY_WIDTH].iter().all(|&pixel| pixel));
+        assert!(sys.display[0..4].iter().all(|&pixel| !pixel));
+    }
+
+    #[test]
+    fn a_removed_and_re_added_breakpoint_traps_again() {
+        let mut sys = System::new(&set_rand_program(0, 0xFF));
+        sys.add_breakpoint(0x200);
+
+        let hit = sys.step();
+        assert!(matches!(hit, Err(ref e) if e.kind == ErrorKind::Breakpoint));
+
+        sys.remove_breakpoint(0x200);
+        sys.add_breakpoint(0x200);
+
+        let hit_again = sys.step();
+        assert!(matches!(hit_again, Err(ref e) if e.kind == ErrorKind::Breakpoint));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,61 @@
+use super::Address;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    MemoryOutOfBounds,
+    StackUnderflow,
+    StackOverflow,
+    InvalidOpcode,
+    InvalidSaveState,
+    Breakpoint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub msg: String,
+}
+
+impl Error {
+    pub fn memory_out_of_bounds(address: Address) -> Error {
+        Error {
+            kind: ErrorKind::MemoryOutOfBounds,
+            msg: format!("address {:#06x} is outside of the 4096 bytes of memory", address),
+        }
+    }
+
+    pub fn stack_underflow() -> Error {
+        Error {
+            kind: ErrorKind::StackUnderflow,
+            msg: "attempted to return with an empty call stack".to_string(),
+        }
+    }
+
+    pub fn stack_overflow(capacity: usize) -> Error {
+        Error {
+            kind: ErrorKind::StackOverflow,
+            msg: format!("call stack exceeded its capacity of {} frames", capacity),
+        }
+    }
+
+    pub fn invalid_opcode(first: u8, second: u8) -> Error {
+        Error {
+            kind: ErrorKind::InvalidOpcode,
+            msg: format!("unrecognized opcode {:02x}{:02x}", first, second),
+        }
+    }
+
+    pub fn invalid_save_state(msg: String) -> Error {
+        Error {
+            kind: ErrorKind::InvalidSaveState,
+            msg,
+        }
+    }
+
+    pub fn breakpoint(address: Address) -> Error {
+        Error {
+            kind: ErrorKind::Breakpoint,
+            msg: format!("hit breakpoint at {:#06x}", address),
+        }
+    }
+}